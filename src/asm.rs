@@ -0,0 +1,69 @@
+use val::{parse_instruction, Instruction};
+
+pub fn assemble(source: &str) -> Result<Vec<Instruction<String>>, String> {
+    let mut instructions = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        // A ';' only starts a comment at the start of a word, so "get a;b" reaches
+        // parse_instruction as the operand "a;b" and is rejected there.
+        let comment = line.char_indices()
+            .find(|&(i, c)| c == ';' && (i == 0 || line[..i].ends_with(char::is_whitespace)))
+            .map(|(i, _)| i);
+        let code = match comment {
+            Some(comment) => &line[..comment],
+            None          => line
+        };
+        let mut words = code.split_whitespace();
+        let inst = match words.next() {
+            Some(inst) => inst,
+            None       => continue
+        };
+        let arg = words.next().map(|arg| arg.to_owned());
+        if let Some(extra) = words.next() {
+            return Err(format!("line {}: unexpected {:?} after {:?}", index + 1, extra, inst));
+        }
+        match parse_instruction(inst, arg) {
+            Ok(instruction) => instructions.push(instruction),
+            Err(error)      => return Err(format!("line {}: {}", index + 1, error))
+        }
+    }
+    Ok(instructions)
+}
+
+#[test]
+fn it_assembles() {
+    let source = "get a\nget b ; comment\n\nadd\nset c\n";
+    assert_eq!(assemble(source), Ok(vec![
+        Instruction::Get("a".to_owned()),
+        Instruction::Get("b".to_owned()),
+        Instruction::Add,
+        Instruction::Set("c".to_owned())
+    ]));
+}
+
+#[test]
+fn it_reports_assembly_errors_by_line() {
+    assert_eq!(assemble("add\nfrobnicate"),
+               Err("line 2: Unknown instruction \"frobnicate\"(None)".to_owned()));
+    assert_eq!(assemble("get a b"),
+               Err("line 1: unexpected \"b\" after \"get\"".to_owned()));
+    assert!(assemble("add\n\nget").unwrap_err().starts_with("line 3:"));
+}
+
+#[test]
+fn it_assembles_displayed_instructions() {
+    let instructions = vec![
+        Instruction::Get("a".to_owned()),
+        Instruction::Negate,
+        Instruction::Set("b".to_owned())
+    ];
+    let source: Vec<String> = instructions.iter().map(|inst| inst.to_string()).collect();
+    assert_eq!(assemble(&source.join("\n")), Ok(instructions));
+}
+
+#[test]
+fn it_rejects_displayed_operands_that_do_not_reassemble() {
+    let spaced = Instruction::Get("a b".to_owned()).to_string();
+    assert!(assemble(&spaced).is_err());
+    let commented = Instruction::Get("a;b".to_owned()).to_string();
+    assert!(assemble(&commented).is_err());
+}
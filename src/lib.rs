@@ -1,4 +1,5 @@
 #![allow(dead_code)]
 
+mod asm;
 mod symbol;
 mod val;
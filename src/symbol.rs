@@ -1,26 +1,61 @@
+use std::borrow::Borrow;
 use std::collections::{HashMap};
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Weak};
+#[cfg(debug)]
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Clone, Debug, Hash)]
+// Identifies the table a symbol came from, so debug builds can catch
+// comparisons between tables. Unlike the table's address, it survives the
+// table being moved.
+#[cfg(debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableId(usize);
+
+#[cfg(debug)]
+impl TableId {
+    fn new() -> Self {
+        static NEXT_TABLE_ID: AtomicUsize = AtomicUsize::new(0);
+        TableId(NEXT_TABLE_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+#[cfg(not(debug))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableId;
+
+#[cfg(not(debug))]
+impl TableId {
+    fn new() -> Self {
+        TableId
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Symbol {
     source: Arc<String>,
     #[cfg(debug)]
-    table: *const Table
+    table: TableId
 }
 
 impl Symbol {
+    pub fn from_str(source: &str, table: TableId) -> Self {
+        Symbol::from_arc(Arc::new(source.to_owned()), table)
+    }
+
     #[cfg(debug)]
-    pub fn from_str(source: &str, table: *const Table) -> Self {
+    fn from_arc(source: Arc<String>, table: TableId) -> Self {
         Symbol {
-            source: Arc::new(source.to_owned()),
-            table: table
+            source,
+            table
         }
     }
 
     #[cfg(not(debug))]
-    pub fn from_str(source: &str, _table: *const Table) -> Self {
+    fn from_arc(source: Arc<String>, _table: TableId) -> Self {
         Symbol {
-            source: Arc::new(source.to_owned())
+            source
         }
     }
 
@@ -37,35 +72,330 @@ impl Symbol {
 impl PartialEq for Symbol {
     fn eq(&self, other: &Self) -> bool {
         self.check_table(other);
-        (&*self.source as *const _) == (&*other.source as *const _)
+        ::std::ptr::eq(&*self.source, &*other.source)
     }
 }
 
 impl Eq for Symbol {}
 
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.source
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.source
+    }
+}
+
+// Symbol hashes its text, and within one table equal text means the same
+// pointer, so maps keyed by symbols from a single table can be queried with a
+// &str. A HashMap<Symbol, _> must not mix symbols from several tables: equal
+// text would then hash alike but compare unequal, and a &str lookup could find
+// either key. Use ContentSymbol keys for that.
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.source
+    }
+}
+
+// Compares and hashes by the interned text instead of by identity, so symbols
+// from different tables can be mixed. Slower than Symbol, which only compares
+// pointers, but equal across tables and runs.
+#[derive(Clone, Debug)]
+pub struct ContentSymbol(pub Symbol);
+
+impl From<Symbol> for ContentSymbol {
+    fn from(symbol: Symbol) -> Self {
+        ContentSymbol(symbol)
+    }
+}
+
+impl PartialEq for ContentSymbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.source == other.0.source
+    }
+}
+
+impl Eq for ContentSymbol {}
+
+impl Hash for ContentSymbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.source.hash(state);
+    }
+}
+
+impl Borrow<str> for ContentSymbol {
+    fn borrow(&self) -> &str {
+        &self.0.source
+    }
+}
+
 pub struct Table {
+    id: TableId,
     symbols: HashMap<Box<str>, Symbol>
 }
 
 impl Table {
     pub fn new() -> Self {
         Table {
+            id: TableId::new(),
             symbols: HashMap::new()
         }
     }
 
+    pub fn with_capacity(capacity: usize) -> Self {
+        Table {
+            id: TableId::new(),
+            symbols: HashMap::with_capacity(capacity)
+        }
+    }
+
+    pub fn from_keywords(keywords: &[&str]) -> (Self, Vec<Symbol>) {
+        let mut table = Table::with_capacity(keywords.len());
+        let symbols = keywords.iter().map(|keyword| table.intern(keyword)).collect();
+        (table, symbols)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.values()
+    }
+
+    pub fn get(&self, source: &str) -> Option<Symbol> {
+        self.symbols.get(source).cloned()
+    }
+
     pub fn intern(&mut self, source: &str) -> Symbol {
         if let Some(symbol) = self.symbols.get(source) {
             return symbol.clone()
         }
-        let new_symbol = Symbol::from_str(source, self);
+        let new_symbol = Symbol::from_str(source, self.id);
         self.symbols.insert(source.to_owned().into_boxed_str(), new_symbol.clone());
         new_symbol
     }
 }
 
+// Holds only weak references, so a symbol is freed once every Symbol for it
+// has been dropped. Interning the same text again after that creates a new
+// symbol, which is not equal to any symbol handed out before.
+pub struct WeakTable {
+    id: TableId,
+    symbols: HashMap<Box<str>, Weak<String>>
+}
+
+impl WeakTable {
+    pub fn new() -> Self {
+        WeakTable {
+            id: TableId::new(),
+            symbols: HashMap::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn intern(&mut self, source: &str) -> Symbol {
+        if let Some(live) = self.symbols.get(source).and_then(Weak::upgrade) {
+            return Symbol::from_arc(live, self.id)
+        }
+        let new_source = Arc::new(source.to_owned());
+        self.symbols.insert(source.to_owned().into_boxed_str(), Arc::downgrade(&new_source));
+        Symbol::from_arc(new_source, self.id)
+    }
+
+    pub fn sweep(&mut self) -> usize {
+        let before = self.symbols.len();
+        self.symbols.retain(|_, symbol| symbol.upgrade().is_some());
+        before - self.symbols.len()
+    }
+}
+
+// Dense ids handed out in interning order, usable as indices into side
+// tables. Ids are only meaningful to the Interner that produced them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(u32);
+
+impl SymbolId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+// Each string is allocated once and shared between the id-to-string Vec and
+// the string-to-id map.
+pub struct Interner {
+    strings: Vec<Arc<str>>,
+    ids: HashMap<Arc<str>, SymbolId>
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner {
+            strings: Vec::new(),
+            ids: HashMap::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn get(&self, source: &str) -> Option<SymbolId> {
+        self.ids.get(source).cloned()
+    }
+
+    pub fn intern(&mut self, source: &str) -> SymbolId {
+        if let Some(&id) = self.ids.get(source) {
+            return id
+        }
+        assert!(self.strings.len() < u32::MAX as usize, "Interner is full");
+        let id = SymbolId(self.strings.len() as u32);
+        let shared: Arc<str> = Arc::from(source);
+        self.strings.push(shared.clone());
+        self.ids.insert(shared, id);
+        id
+    }
+
+    pub fn resolve(&self, id: SymbolId) -> &str {
+        &self.strings[id.index()]
+    }
+}
+
 #[test]
+#[allow(clippy::unnecessary_to_owned)]
 fn it_interns() {
     let mut tab = Table::new();
     assert_eq!(tab.intern("test"), tab.intern(&"test".to_owned()));
 }
+
+#[test]
+fn it_counts_and_iterates() {
+    let mut tab = Table::with_capacity(4);
+    assert!(tab.is_empty());
+    let symbols = vec![tab.intern("a"), tab.intern("b"), tab.intern("c")];
+    tab.intern("a");
+    assert_eq!(tab.len(), 3);
+    let seen: Vec<&Symbol> = tab.iter().collect();
+    assert_eq!(seen.len(), 3);
+    for symbol in &symbols {
+        assert!(seen.contains(&symbol));
+    }
+}
+
+#[test]
+fn it_compares_content_symbols_across_tables() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+
+    fn hash_of(symbol: &ContentSymbol) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        symbol.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let mut first = Table::new();
+    let mut second = Table::new();
+    let a = ContentSymbol::from(first.intern("name"));
+    let b = ContentSymbol::from(second.intern("name"));
+    assert_eq!(a, b);
+    assert_eq!(hash_of(&a), hash_of(&b));
+    assert!(a != ContentSymbol::from(second.intern("other")));
+    let set: HashSet<ContentSymbol> = vec![a, b].into_iter().collect();
+    assert!(set.contains("name"));
+}
+
+#[test]
+fn it_gets_only_interned_symbols() {
+    let mut tab = Table::new();
+    assert_eq!(tab.get("test"), None);
+    let symbol = tab.intern("test");
+    assert_eq!(tab.get("test"), Some(symbol));
+    assert_eq!(tab.len(), 1);
+}
+
+#[test]
+fn it_sweeps_dropped_symbols() {
+    let mut tab = WeakTable::new();
+    let kept = tab.intern("kept");
+    assert_eq!(tab.intern("kept"), kept);
+    drop(tab.intern("dropped"));
+    assert_eq!(tab.len(), 2);
+    assert_eq!(tab.sweep(), 1);
+    assert_eq!(tab.len(), 1);
+    assert_eq!(tab.sweep(), 0);
+    assert_eq!(tab.intern("kept"), kept);
+}
+
+#[test]
+fn it_compares_weak_symbols_after_the_table_moves() {
+    let mut tab = WeakTable::new();
+    let before = tab.intern("before");
+    let mut moved = Box::new(tab);
+    assert!(moved.intern("after") != before);
+    assert_eq!(moved.intern("before"), before);
+}
+
+#[test]
+fn it_derefs_to_str() {
+    fn length(text: &str) -> usize {
+        text.len()
+    }
+
+    let mut tab = Table::new();
+    let symbol = tab.intern("test");
+    assert_eq!(length(&symbol), 4);
+    assert_eq!(symbol.as_ref(), "test");
+    let mut map = HashMap::new();
+    map.insert(symbol, 1);
+    assert_eq!(map.get("test"), Some(&1));
+}
+
+#[test]
+fn it_preseeds_keywords() {
+    let (mut tab, keywords) = Table::from_keywords(&["if", "else", "if"]);
+    assert_eq!(tab.len(), 2);
+    assert_eq!(keywords.len(), 3);
+    assert_eq!(keywords[0], keywords[2]);
+    assert_eq!(tab.intern("if"), keywords[0]);
+    assert_eq!(tab.intern("else"), keywords[1]);
+    assert!(tab.intern("x") != keywords[0]);
+}
+
+#[test]
+fn it_interns_to_dense_ids() {
+    let mut interner = Interner::new();
+    let a = interner.intern("a");
+    let b = interner.intern("b");
+    assert_eq!(interner.intern("a"), a);
+    assert_eq!((a.index(), b.index()), (0, 1));
+    assert_eq!(interner.resolve(b), "b");
+    assert_eq!(interner.get("c"), None);
+    assert_eq!(interner.len(), 2);
+}
@@ -1,13 +1,5 @@
-use std::u8;
-use std::u16;
-use std::u32;
-use std::u64;
-use std::i8;
-use std::i16;
-use std::i32;
-use std::i64;
-use std::f32;
-use std::f64;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Type {
@@ -29,9 +21,11 @@ pub fn number_tag_to_type(tag: &str) -> Result<Type, String> {
         "u8"  => Ok(Type::U8),
         "u16" => Ok(Type::U16),
         "u32" => Ok(Type::U32),
+        "u64" => Ok(Type::U64),
         "i8"  => Ok(Type::I8),
         "i16" => Ok(Type::I16),
         "i32" => Ok(Type::I32),
+        "i64" => Ok(Type::I64),
         "f32" => Ok(Type::F32),
         "f64" => Ok(Type::F64),
         tag   => Err(format!("Uknown numeric tag {}", tag))
@@ -39,18 +33,23 @@ pub fn number_tag_to_type(tag: &str) -> Result<Type, String> {
 }
 
 
+// Floats report the largest magnitude up to which every integer is exactly
+// representable (2^24 for f32, 2^53 for f64), rather than their maximum value.
+const F32_MAX_EXACT_INTEGER: u64 = 1 << f32::MANTISSA_DIGITS;
+const F64_MAX_EXACT_INTEGER: u64 = 1 << f64::MANTISSA_DIGITS;
+
 pub fn max_integer_value_of_type(tp: &Type) -> u64 {
     match *tp {
         Type::U8  => u8::MAX as u64,
         Type::U16 => u16::MAX as u64,
         Type::U32 => u32::MAX as u64,
-        Type::U64 => u64::MAX as u64,
+        Type::U64 => u64::MAX,
         Type::I8  => i8::MAX as u64,
         Type::I16 => i16::MAX as u64,
         Type::I32 => i32::MAX as u64,
         Type::I64 => i64::MAX as u64,
-        Type::F32 => f32::MAX as u64,
-        Type::F64 => f64::MAX as u64,
+        Type::F32 => F32_MAX_EXACT_INTEGER,
+        Type::F64 => F64_MAX_EXACT_INTEGER,
         _         => panic!("Expected numeric type")
     }
 }
@@ -64,9 +63,9 @@ pub fn min_integer_value_of_type(tp: &Type) -> i64 {
         Type::I8  => i8::MIN as i64,
         Type::I16 => i16::MIN as i64,
         Type::I32 => i32::MIN as i64,
-        Type::I64 => i64::MIN as i64,
-        Type::F32 => f32::MIN as i64,
-        Type::F64 => f64::MIN as i64,
+        Type::I64 => i64::MIN,
+        Type::F32 => -(F32_MAX_EXACT_INTEGER as i64),
+        Type::F64 => -(F64_MAX_EXACT_INTEGER as i64),
         _         => panic!("Expected numeric type")
     }
 }
@@ -74,8 +73,9 @@ pub fn min_integer_value_of_type(tp: &Type) -> i64 {
 pub fn integer_fits_in_type(number: u64, negative: bool, tp: &Type) -> bool {
     if negative {
         if number > 0 {
+            let min = min_integer_value_of_type(tp);
             // This handles the one more negative value allowed by twos completement.
-            number - 1 <= -(min_integer_value_of_type(tp) + 1) as u64
+            min < 0 && number - 1 <= -(min + 1) as u64
         } else {
             true
         }
@@ -95,11 +95,11 @@ fn make_negative_i64(number: u64) -> i64 {
 
 pub fn shrink_integer(number: u64, negative: bool, target_type: &Type) -> Option<Number> {
     if integer_fits_in_type(number, negative, target_type) {
-        if negative {
+        if negative && number != 0 {
             match *target_type {
-                Type::I8  => Some(Number::I8(-(number as i8))),
-                Type::I16 => Some(Number::I16(-(number as i16))),
-                Type::I32 => Some(Number::I32(-(number as i32))),
+                Type::I8  => Some(Number::I8(make_negative_i64(number) as i8)),
+                Type::I16 => Some(Number::I16(make_negative_i64(number) as i16)),
+                Type::I32 => Some(Number::I32(make_negative_i64(number) as i32)),
                 Type::I64 => Some(Number::I64(make_negative_i64(number))),
                 Type::F32 => Some(Number::F32(-(number as f32))),
                 Type::F64 => Some(Number::F64(-(number as f64))),
@@ -110,7 +110,7 @@ pub fn shrink_integer(number: u64, negative: bool, target_type: &Type) -> Option
                 Type::U8  => Some(Number::U8(number as u8)),
                 Type::U16 => Some(Number::U16(number as u16)),
                 Type::U32 => Some(Number::U32(number as u32)),
-                Type::U64 => Some(Number::U64(number as u64)),
+                Type::U64 => Some(Number::U64(number)),
                 Type::I8  => Some(Number::I8(number as i8)),
                 Type::I16 => Some(Number::I16(number as i16)),
                 Type::I32 => Some(Number::I32(number as i32)),
@@ -139,6 +139,136 @@ pub enum Number {
     F64(f64),
 }
 
+impl Number {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Number::U8(n)  => n as f64,
+            Number::U16(n) => n as f64,
+            Number::U32(n) => n as f64,
+            Number::U64(n) => n as f64,
+            Number::I8(n)  => n as f64,
+            Number::I16(n) => n as f64,
+            Number::I32(n) => n as f64,
+            Number::I64(n) => n as f64,
+            Number::F32(n) => n as f64,
+            Number::F64(n) => n
+        }
+    }
+
+    pub fn as_i64(self) -> Option<i64> {
+        match self.to_type(&Type::I64) {
+            Some(Number::I64(n)) => Some(n),
+            _                    => None
+        }
+    }
+
+    // Integer targets and integer sources must convert exactly, going through
+    // shrink_integer. Float to float conversions round like `as` does.
+    pub fn to_type(self, tp: &Type) -> Option<Number> {
+        match (self, tp) {
+            (_, &Type::Object) => None,
+            (Number::F32(_), &Type::F32) | (Number::F64(_), &Type::F32) => {
+                // Rounding is accepted, but a finite value too large for f32
+                // does not fit rather than becoming infinity.
+                let value = self.as_f64();
+                if value.is_finite() && (value as f32).is_infinite() {
+                    None
+                } else {
+                    Some(Number::F32(value as f32))
+                }
+            }
+            (Number::F32(_), &Type::F64) | (Number::F64(_), &Type::F64) => {
+                Some(Number::F64(self.as_f64()))
+            }
+            _ => {
+                self.integer_parts().and_then(|(number, negative)| {
+                    shrink_integer(number, negative, tp)
+                })
+            }
+        }
+    }
+
+    fn integer_parts(self) -> Option<(u64, bool)> {
+        match self {
+            Number::U8(n)  => Some((n as u64, false)),
+            Number::U16(n) => Some((n as u64, false)),
+            Number::U32(n) => Some((n as u64, false)),
+            Number::U64(n) => Some((n, false)),
+            Number::I8(n)  => Some((n.unsigned_abs() as u64, n < 0)),
+            Number::I16(n) => Some((n.unsigned_abs() as u64, n < 0)),
+            Number::I32(n) => Some((n.unsigned_abs() as u64, n < 0)),
+            Number::I64(n) => Some((n.unsigned_abs(), n < 0)),
+            Number::F32(_) |
+            Number::F64(_) => {
+                let n = self.as_f64();
+                if n.is_finite() && n.fract() == 0.0 && n.abs() < u64::MAX as f64 {
+                    Some((n.abs() as u64, n < 0.0))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Finite floats use the Debug formatting, which always keeps a decimal
+        // point or exponent and prints the shortest digits that round-trip.
+        // NaN and the infinities come out as NaNf64, inff64 and -inff64, which
+        // are not number literals and do not round-trip.
+        match *self {
+            Number::U8(n)  => write!(f, "{}u8", n),
+            Number::U16(n) => write!(f, "{}u16", n),
+            Number::U32(n) => write!(f, "{}u32", n),
+            Number::U64(n) => write!(f, "{}u64", n),
+            Number::I8(n)  => write!(f, "{}i8", n),
+            Number::I16(n) => write!(f, "{}i16", n),
+            Number::I32(n) => write!(f, "{}i32", n),
+            Number::I64(n) => write!(f, "{}i64", n),
+            Number::F32(n) => write!(f, "{:?}f32", n),
+            Number::F64(n) => write!(f, "{:?}f64", n)
+        }
+    }
+}
+
+// Compares floats by their bits, so a NaN equals itself and 0.0 and -0.0 are
+// different keys. This is stricter than the == on Number, but gives an Eq and
+// Hash that can key a HashMap.
+#[derive(Debug, Clone, Copy)]
+pub struct HashableNumber(pub Number);
+
+impl HashableNumber {
+    fn bits(&self) -> (u8, u64) {
+        match self.0 {
+            Number::U8(n)  => (0, n as u64),
+            Number::U16(n) => (1, n as u64),
+            Number::U32(n) => (2, n as u64),
+            Number::U64(n) => (3, n),
+            Number::I8(n)  => (4, n as u64),
+            Number::I16(n) => (5, n as u64),
+            Number::I32(n) => (6, n as u64),
+            Number::I64(n) => (7, n as u64),
+            Number::F32(n) => (8, n.to_bits() as u64),
+            Number::F64(n) => (9, n.to_bits())
+        }
+    }
+}
+
+impl PartialEq for HashableNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for HashableNumber {}
+
+impl Hash for HashableNumber {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Instruction<A> {
     Get(A),
@@ -153,72 +283,456 @@ pub enum Instruction<A> {
     Branch(A),
     Blank,
     Halt,
+    Print,
     Construct,
+    Concat,
     Store(A),
     Load(A),
+    // The operand is a literal integer in the i64 range, pushed directly
+    // instead of going through the constant pool.
+    Loadi(A),
     Add,
     Subtract,
     Multiply,
+    AddSat,
+    SubSat,
+    MulSat,
+    AddWrap,
+    SubWrap,
+    MulWrap,
     Divide,
     Modulus,
+    ModulusEuclid,
+    // 0**0 is 1 for integers and floats alike, as with i64::pow and f64::powf. A
+    // negative integer exponent is an error rather than a float result.
+    Power,
+    // Min and Max follow f64::min and f64::max: a NaN operand is ignored in favor
+    // of the other one, so the result is NaN only when both operands are.
+    Min,
+    Max,
+    Negate,
+    Abs,
+    // Pushes an integer -1, 0 or 1. Both -0.0 and 0.0 give 0, and NaN is an error
+    // since it has no sign to report.
+    Sign,
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
     Is,
     Less,
     LessEqual,
     Equal,
     Greater,
     GreaterEqual,
+    // Pops the condition, then the value for false, then the value for true, and
+    // pushes the value for true if the condition is true, else the value for false.
+    // Net effect: [if_true, if_false, condition] -> [chosen].
+    Select,
     Right,
     Left,
+    LeftWrap,
     And,
     Or,
     Xor,
     Not,
+    CountOnes,
+    LeadingZeros,
+    TrailingZeros,
+    LogicalAnd,
+    LogicalOr,
     Grow(A),
     Shrink(A),
     F2I,
-    I2F
+    I2F,
+    Cast(A)
 }
 
-fn parse_instruction(inst: &str, arg: Option<String>) -> Result<Instruction<String>, String> {
+// Operands are written verbatim. parse_instruction rejects operands containing
+// whitespace or ';', so only instructions it accepts print as valid assembly.
+impl<A: fmt::Display> fmt::Display for Instruction<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match instruction_argument(self) {
+            Some(arg) => write!(f, "{} {}", instruction_mnemonic(self), arg),
+            None      => write!(f, "{}", instruction_mnemonic(self))
+        }
+    }
+}
+
+pub fn parse_instruction(inst: &str, arg: Option<String>) -> Result<Instruction<String>, String> {
+    if let Some(ref arg) = arg {
+        if arg.contains(char::is_whitespace) || arg.contains(';') {
+            return Err(format!("Invalid operand {:?} for {:?}", arg, inst));
+        }
+    }
     match (inst, arg) {
-        ("get", Some(arg))     => Ok(Instruction::Get(arg)),
-        ("set", Some(arg))     => Ok(Instruction::Set(arg)),
-        ("push", None)         => Ok(Instruction::Push),
-        ("pop", Some(arg))     => Ok(Instruction::Pop(arg)),
-        ("call", Some(arg))    => Ok(Instruction::Call(arg)),
-        ("return", None)       => Ok(Instruction::Return),
-        ("throw", None)        => Ok(Instruction::Throw),
-        ("catch", None)        => Ok(Instruction::Catch),
-        ("jump", Some(arg))    => Ok(Instruction::Jump(arg)),
-        ("branch", Some(arg))  => Ok(Instruction::Branch(arg)),
-        ("blank", None)        => Ok(Instruction::Blank),
-        ("halt", None)         => Ok(Instruction::Halt),
-        ("construct", None)    => Ok(Instruction::Construct),
-        ("store", Some(arg))   => Ok(Instruction::Store(arg)),
-        ("load", Some(arg))    => Ok(Instruction::Load(arg)),
-        ("is", None)           => Ok(Instruction::Is),
-        ("add", None)          => Ok(Instruction::Add),
-        ("subtract", None)     => Ok(Instruction::Subtract),
-        ("multiply", None)     => Ok(Instruction::Multiply),
-        ("divide", None)       => Ok(Instruction::Divide),
-        ("modulus", None)      => Ok(Instruction::Modulus),
-        ("less", None)         => Ok(Instruction::Less),
-        ("lessequal", None)    => Ok(Instruction::LessEqual),
-        ("equal", None)        => Ok(Instruction::Equal),
-        ("greater", None)      => Ok(Instruction::Greater),
-        ("greaterequal", None) => Ok(Instruction::GreaterEqual),
-        ("right", None)        => Ok(Instruction::Right),
-        ("left", None)         => Ok(Instruction::Left),
-        ("and", None)          => Ok(Instruction::And),
-        ("or", None)           => Ok(Instruction::Or),
-        ("xor", None)          => Ok(Instruction::Xor),
-        ("not", None)          => Ok(Instruction::Not),
-        ("grow", Some(arg))    => Ok(Instruction::Grow(arg)),
-        ("shrink", Some(arg))  => Ok(Instruction::Shrink(arg)),
-        ("f2i", None)          => Ok(Instruction::F2I),
-        ("i2f", None)          => Ok(Instruction::I2F),
-        (inst, arg)            => {
+        ("get", Some(arg))      => Ok(Instruction::Get(arg)),
+        ("set", Some(arg))      => Ok(Instruction::Set(arg)),
+        ("push", None)          => Ok(Instruction::Push),
+        ("pop", Some(arg))      => Ok(Instruction::Pop(arg)),
+        ("call", Some(arg))     => Ok(Instruction::Call(arg)),
+        ("return", None)        => Ok(Instruction::Return),
+        ("throw", None)         => Ok(Instruction::Throw),
+        ("catch", None)         => Ok(Instruction::Catch),
+        ("jump", Some(arg))     => Ok(Instruction::Jump(arg)),
+        ("branch", Some(arg))   => Ok(Instruction::Branch(arg)),
+        ("blank", None)         => Ok(Instruction::Blank),
+        ("halt", None)          => Ok(Instruction::Halt),
+        ("print", None)         => Ok(Instruction::Print),
+        ("construct", None)     => Ok(Instruction::Construct),
+        ("concat", None)        => Ok(Instruction::Concat),
+        ("store", Some(arg))    => Ok(Instruction::Store(arg)),
+        ("load", Some(arg))     => Ok(Instruction::Load(arg)),
+        ("loadi", Some(arg))    => {
+          arg.parse::<i64>().map_err(|_| format!("Invalid loadi operand {:?}", arg))?;
+          Ok(Instruction::Loadi(arg))
+        }
+        ("is", None)            => Ok(Instruction::Is),
+        ("add", None)           => Ok(Instruction::Add),
+        ("subtract", None)      => Ok(Instruction::Subtract),
+        ("multiply", None)      => Ok(Instruction::Multiply),
+        ("addsat", None)        => Ok(Instruction::AddSat),
+        ("subsat", None)        => Ok(Instruction::SubSat),
+        ("mulsat", None)        => Ok(Instruction::MulSat),
+        ("addwrap", None)       => Ok(Instruction::AddWrap),
+        ("subwrap", None)       => Ok(Instruction::SubWrap),
+        ("mulwrap", None)       => Ok(Instruction::MulWrap),
+        ("divide", None)        => Ok(Instruction::Divide),
+        ("modulus", None)       => Ok(Instruction::Modulus),
+        ("moduluseuclid", None) => Ok(Instruction::ModulusEuclid),
+        ("power", None)         => Ok(Instruction::Power),
+        ("min", None)           => Ok(Instruction::Min),
+        ("max", None)           => Ok(Instruction::Max),
+        ("negate", None)        => Ok(Instruction::Negate),
+        ("abs", None)           => Ok(Instruction::Abs),
+        ("sign", None)          => Ok(Instruction::Sign),
+        ("floor", None)         => Ok(Instruction::Floor),
+        ("ceil", None)          => Ok(Instruction::Ceil),
+        ("round", None)         => Ok(Instruction::Round),
+        ("trunc", None)         => Ok(Instruction::Trunc),
+        ("less", None)          => Ok(Instruction::Less),
+        ("lessequal", None)     => Ok(Instruction::LessEqual),
+        ("equal", None)         => Ok(Instruction::Equal),
+        ("greater", None)       => Ok(Instruction::Greater),
+        ("greaterequal", None)  => Ok(Instruction::GreaterEqual),
+        ("select", None)        => Ok(Instruction::Select),
+        ("right", None)         => Ok(Instruction::Right),
+        ("left", None)          => Ok(Instruction::Left),
+        ("leftwrap", None)      => Ok(Instruction::LeftWrap),
+        ("and", None)           => Ok(Instruction::And),
+        ("or", None)            => Ok(Instruction::Or),
+        ("xor", None)           => Ok(Instruction::Xor),
+        ("not", None)           => Ok(Instruction::Not),
+        ("countones", None)     => Ok(Instruction::CountOnes),
+        ("leadingzeros", None)  => Ok(Instruction::LeadingZeros),
+        ("trailingzeros", None) => Ok(Instruction::TrailingZeros),
+        ("logicaland", None)    => Ok(Instruction::LogicalAnd),
+        ("logicalor", None)     => Ok(Instruction::LogicalOr),
+        ("grow", Some(arg))     => Ok(Instruction::Grow(arg)),
+        ("shrink", Some(arg))   => Ok(Instruction::Shrink(arg)),
+        ("f2i", None)           => Ok(Instruction::F2I),
+        ("i2f", None)           => Ok(Instruction::I2F),
+        ("cast", Some(arg))     => {
+          number_tag_to_type(&arg)?;
+          Ok(Instruction::Cast(arg))
+        }
+        (inst, arg)             => {
           Err(format!("Unknown instruction {:?}({:?})", inst, arg))
         }
     }
 }
+
+pub fn instruction_mnemonic<A>(inst: &Instruction<A>) -> &'static str {
+    match *inst {
+        Instruction::Get(_)        => "get",
+        Instruction::Set(_)        => "set",
+        Instruction::Push          => "push",
+        Instruction::Pop(_)        => "pop",
+        Instruction::Call(_)       => "call",
+        Instruction::Return        => "return",
+        Instruction::Throw         => "throw",
+        Instruction::Catch         => "catch",
+        Instruction::Jump(_)       => "jump",
+        Instruction::Branch(_)     => "branch",
+        Instruction::Blank         => "blank",
+        Instruction::Halt          => "halt",
+        Instruction::Print         => "print",
+        Instruction::Construct     => "construct",
+        Instruction::Concat        => "concat",
+        Instruction::Store(_)      => "store",
+        Instruction::Load(_)       => "load",
+        Instruction::Loadi(_)      => "loadi",
+        Instruction::Is            => "is",
+        Instruction::Add           => "add",
+        Instruction::Subtract      => "subtract",
+        Instruction::Multiply      => "multiply",
+        Instruction::AddSat        => "addsat",
+        Instruction::SubSat        => "subsat",
+        Instruction::MulSat        => "mulsat",
+        Instruction::AddWrap       => "addwrap",
+        Instruction::SubWrap       => "subwrap",
+        Instruction::MulWrap       => "mulwrap",
+        Instruction::Divide        => "divide",
+        Instruction::Modulus       => "modulus",
+        Instruction::ModulusEuclid => "moduluseuclid",
+        Instruction::Power         => "power",
+        Instruction::Min           => "min",
+        Instruction::Max           => "max",
+        Instruction::Negate        => "negate",
+        Instruction::Abs           => "abs",
+        Instruction::Sign          => "sign",
+        Instruction::Floor         => "floor",
+        Instruction::Ceil          => "ceil",
+        Instruction::Round         => "round",
+        Instruction::Trunc         => "trunc",
+        Instruction::Less          => "less",
+        Instruction::LessEqual     => "lessequal",
+        Instruction::Equal         => "equal",
+        Instruction::Greater       => "greater",
+        Instruction::GreaterEqual  => "greaterequal",
+        Instruction::Select        => "select",
+        Instruction::Right         => "right",
+        Instruction::Left          => "left",
+        Instruction::LeftWrap      => "leftwrap",
+        Instruction::And           => "and",
+        Instruction::Or            => "or",
+        Instruction::Xor           => "xor",
+        Instruction::Not           => "not",
+        Instruction::CountOnes     => "countones",
+        Instruction::LeadingZeros  => "leadingzeros",
+        Instruction::TrailingZeros => "trailingzeros",
+        Instruction::LogicalAnd    => "logicaland",
+        Instruction::LogicalOr     => "logicalor",
+        Instruction::Grow(_)       => "grow",
+        Instruction::Shrink(_)     => "shrink",
+        Instruction::F2I           => "f2i",
+        Instruction::I2F           => "i2f",
+        Instruction::Cast(_)       => "cast"
+    }
+}
+
+pub fn instruction_argument<A>(inst: &Instruction<A>) -> Option<&A> {
+    match *inst {
+        Instruction::Get(ref arg) |
+        Instruction::Set(ref arg) |
+        Instruction::Pop(ref arg) |
+        Instruction::Call(ref arg) |
+        Instruction::Jump(ref arg) |
+        Instruction::Branch(ref arg) |
+        Instruction::Store(ref arg) |
+        Instruction::Load(ref arg) |
+        Instruction::Grow(ref arg) |
+        Instruction::Shrink(ref arg) |
+        Instruction::Loadi(ref arg) |
+        Instruction::Cast(ref arg) => Some(arg),
+        _ => None
+    }
+}
+
+#[test]
+fn it_displays_numbers() {
+    assert_eq!(format!("{}", Number::U8(255)), "255u8");
+    assert_eq!(format!("{}", Number::I32(-5)), "-5i32");
+    assert_eq!(format!("{}", Number::F32(1.5)), "1.5f32");
+    assert_eq!(format!("{}", Number::F64(1.0)), "1.0f64");
+    assert_eq!(format!("{}", Number::F64(f64::NAN)), "NaNf64");
+    assert_eq!(format!("{}", Number::F64(f64::INFINITY)), "inff64");
+    assert_eq!(format!("{}", Number::F32(f32::NEG_INFINITY)), "-inff32");
+}
+
+#[test]
+fn it_round_trips_displayed_numbers() {
+    fn parse_suffixed_number(text: &str) -> Number {
+        let at = text.find(&['u', 'i', 'f'][..]).unwrap();
+        let (digits, tag) = text.split_at(at);
+        match number_tag_to_type(tag).unwrap() {
+            Type::F32 => Number::F32(digits.parse().unwrap()),
+            Type::F64 => Number::F64(digits.parse().unwrap()),
+            tp        => {
+                let negative = digits.starts_with('-');
+                let magnitude = digits.trim_start_matches('-').parse().unwrap();
+                shrink_integer(magnitude, negative, &tp).unwrap()
+            }
+        }
+    }
+
+    let numbers = [
+        Number::U8(255),
+        Number::U16(1000),
+        Number::U32(7),
+        Number::U64(u64::MAX),
+        Number::I8(-100),
+        Number::I16(12),
+        Number::I32(-5),
+        Number::I64(i64::MAX),
+        Number::F32(0.1),
+        Number::F64(1.0),
+        Number::F64(-1e300),
+        Number::F64(0.1 + 0.2),
+    ];
+    for number in &numbers {
+        assert_eq!(parse_suffixed_number(&format!("{}", number)), *number);
+    }
+}
+
+#[test]
+fn it_round_trips_every_instruction_through_its_mnemonic() {
+    let instructions = vec![
+        Instruction::Get("x".to_owned()),
+        Instruction::Set("x".to_owned()),
+        Instruction::Push,
+        Instruction::Pop("x".to_owned()),
+        Instruction::Call("x".to_owned()),
+        Instruction::Return,
+        Instruction::Throw,
+        Instruction::Catch,
+        Instruction::Jump("x".to_owned()),
+        Instruction::Branch("x".to_owned()),
+        Instruction::Blank,
+        Instruction::Halt,
+        Instruction::Print,
+        Instruction::Construct,
+        Instruction::Concat,
+        Instruction::Store("x".to_owned()),
+        Instruction::Load("x".to_owned()),
+        Instruction::Loadi("-5".to_owned()),
+        Instruction::Is,
+        Instruction::Add,
+        Instruction::Subtract,
+        Instruction::Multiply,
+        Instruction::AddSat,
+        Instruction::SubSat,
+        Instruction::MulSat,
+        Instruction::AddWrap,
+        Instruction::SubWrap,
+        Instruction::MulWrap,
+        Instruction::Divide,
+        Instruction::Modulus,
+        Instruction::ModulusEuclid,
+        Instruction::Power,
+        Instruction::Min,
+        Instruction::Max,
+        Instruction::Negate,
+        Instruction::Abs,
+        Instruction::Sign,
+        Instruction::Floor,
+        Instruction::Ceil,
+        Instruction::Round,
+        Instruction::Trunc,
+        Instruction::Less,
+        Instruction::LessEqual,
+        Instruction::Equal,
+        Instruction::Greater,
+        Instruction::GreaterEqual,
+        Instruction::Select,
+        Instruction::Right,
+        Instruction::Left,
+        Instruction::LeftWrap,
+        Instruction::And,
+        Instruction::Or,
+        Instruction::Xor,
+        Instruction::Not,
+        Instruction::CountOnes,
+        Instruction::LeadingZeros,
+        Instruction::TrailingZeros,
+        Instruction::LogicalAnd,
+        Instruction::LogicalOr,
+        Instruction::Grow("x".to_owned()),
+        Instruction::Shrink("x".to_owned()),
+        Instruction::F2I,
+        Instruction::I2F,
+        Instruction::Cast("u8".to_owned()),
+    ];
+    for inst in instructions {
+        let mnemonic = instruction_mnemonic(&inst);
+        let arg = instruction_argument(&inst).cloned();
+        assert_eq!(parse_instruction(mnemonic, arg), Ok(inst));
+    }
+}
+
+#[test]
+fn it_rejects_wrong_operand_counts() {
+    let cases = [
+        ("get", true), ("set", true), ("pop", true), ("call", true),
+        ("jump", true), ("branch", true), ("store", true), ("load", true),
+        ("loadi", true), ("grow", true), ("shrink", true), ("cast", true),
+        ("push", false), ("add", false), ("negate", false), ("select", false)
+    ];
+    for &(mnemonic, takes_operand) in cases.iter() {
+        let wrong = if takes_operand { None } else { Some("x".to_owned()) };
+        assert!(parse_instruction(mnemonic, wrong).is_err(), "{}", mnemonic);
+    }
+}
+
+#[test]
+fn it_rejects_unknown_cast_types() {
+    assert!(parse_instruction("cast", Some("x".to_owned())).is_err());
+}
+
+#[test]
+fn it_rejects_non_integer_immediates() {
+    assert!(parse_instruction("loadi", Some("x".to_owned())).is_err());
+    assert!(parse_instruction("loadi", Some("1.5".to_owned())).is_err());
+    assert!(parse_instruction("loadi", Some("9223372036854775808".to_owned())).is_err());
+}
+
+#[test]
+fn it_fits_only_exact_integers_in_floats() {
+    let limit = 1u64 << 53;
+    assert!(integer_fits_in_type(limit, false, &Type::F64));
+    assert!(!integer_fits_in_type(limit + 1, false, &Type::F64));
+    assert!(integer_fits_in_type(limit, true, &Type::F64));
+    assert!(!integer_fits_in_type(limit + 1, true, &Type::F64));
+    assert!(integer_fits_in_type(1 << 24, false, &Type::F32));
+    assert!(!integer_fits_in_type((1 << 24) + 1, false, &Type::F32));
+    assert_eq!(shrink_integer(limit, true, &Type::F64), Some(Number::F64(-(limit as f64))));
+}
+
+#[test]
+fn it_keys_numbers_by_bits() {
+    use std::collections::HashMap;
+
+    let mut map = HashMap::new();
+    map.insert(HashableNumber(Number::F64(0.0)), "zero");
+    map.insert(HashableNumber(Number::F64(-0.0)), "negative zero");
+    map.insert(HashableNumber(Number::F64(f64::NAN)), "nan");
+    map.insert(HashableNumber(Number::I8(-1)), "i8");
+    map.insert(HashableNumber(Number::I16(-1)), "i16");
+    assert_eq!(map.len(), 5);
+    assert_eq!(map[&HashableNumber(Number::F64(-0.0))], "negative zero");
+    assert_eq!(map[&HashableNumber(Number::F64(f64::NAN))], "nan");
+}
+
+#[test]
+fn it_displays_instructions() {
+    assert_eq!(format!("{}", Instruction::<String>::Add), "add");
+    assert_eq!(format!("{}", Instruction::Get("a".to_owned())), "get a");
+    assert_eq!(format!("{}", Instruction::Jump(3)), "jump 3");
+}
+
+#[test]
+fn it_shrinks_integers_at_the_edges() {
+    assert_eq!(shrink_integer(128, true, &Type::I8), Some(Number::I8(-128)));
+    assert_eq!(shrink_integer(129, true, &Type::I8), None);
+    assert_eq!(shrink_integer(1 << 31, true, &Type::I32), Some(Number::I32(i32::MIN)));
+    assert_eq!(shrink_integer(1, true, &Type::U8), None);
+    assert_eq!(shrink_integer(0, true, &Type::U8), Some(Number::U8(0)));
+}
+
+#[test]
+fn it_converts_numbers() {
+    assert_eq!(Number::U8(200).as_f64(), 200.0);
+    assert_eq!(Number::U64(u64::MAX).as_f64(), u64::MAX as f64);
+    assert_eq!(Number::I8(-128).as_i64(), Some(-128));
+    assert_eq!(Number::U64(u64::MAX).as_i64(), None);
+    assert_eq!(Number::F64(-3.0).as_i64(), Some(-3));
+    assert_eq!(Number::F64(2.5).as_i64(), None);
+    assert_eq!(Number::I32(200).to_type(&Type::U8), Some(Number::U8(200)));
+    assert_eq!(Number::I32(300).to_type(&Type::U8), None);
+    assert_eq!(Number::I16(-1).to_type(&Type::U16), None);
+    assert_eq!(Number::U32(5).to_type(&Type::F32), Some(Number::F32(5.0)));
+    assert_eq!(Number::F64(0.1).to_type(&Type::F32), Some(Number::F32(0.1)));
+    assert_eq!(Number::F64(1e300).to_type(&Type::F32), None);
+    assert_eq!(Number::F64(f64::INFINITY).to_type(&Type::F32), Some(Number::F32(f32::INFINITY)));
+    assert_eq!(Number::U8(1).to_type(&Type::Object), None);
+}